@@ -0,0 +1,183 @@
+// Copyright (C) 2020 Stephane Raux. Distributed under the MIT license.
+
+//! A generic base58-with-checksum codec, reusable beyond [`crate::AccountId`]
+//! (which is built on top of it) for any scheme that appends a checksum to
+//! a payload before base58-encoding it.
+
+use crate::Hashing;
+#[cfg(feature = "default-crypto")]
+use crate::DefaultHashing;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+
+/// Selects the checksum scheme used by [`encode`]/[`decode`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChecksumKind {
+    /// SS58's blake2b-512-truncated checksum.
+    ///
+    /// `checksum_len` is the number of checksum bytes to append; use
+    /// [`ChecksumKind::ss58_for_body_len`] to pick the value Substrate
+    /// uses for a given account body length.
+    Ss58 { checksum_len: usize },
+    /// Bitcoin's double-SHA256 checksum (4 bytes).
+    #[cfg(feature = "default-crypto")]
+    Bitcoin,
+}
+
+impl ChecksumKind {
+    /// The SS58 checksum length used for an account body of `body_len`
+    /// bytes.
+    ///
+    /// See <https://github.com/paritytech/substrate/wiki/External-Address-Format-(SS58)>.
+    pub fn ss58_for_body_len(body_len: usize) -> Self {
+        let checksum_len = match body_len {
+            1 | 2 | 4 | 8 => 1,
+            _ => 2,
+        };
+        ChecksumKind::Ss58 { checksum_len }
+    }
+
+    /// Checksum length, clamped to the 64-byte blake2b-512 digest
+    /// `compute_checksum` produces for the `Ss58` variant, since a caller
+    /// can construct `Ss58 { checksum_len }` directly with an arbitrary
+    /// value.
+    fn checksum_len(self) -> usize {
+        match self {
+            ChecksumKind::Ss58 { checksum_len } => checksum_len.min(64),
+            #[cfg(feature = "default-crypto")]
+            ChecksumKind::Bitcoin => 4,
+        }
+    }
+}
+
+/// Encodes `payload` as base58 with an appended checksum, using
+/// [`DefaultHashing`].
+#[cfg(feature = "default-crypto")]
+pub fn encode(payload: &[u8], checksum: ChecksumKind) -> String {
+    encode_with_hashing(payload, checksum, &DefaultHashing)
+}
+
+/// Encodes `payload` as base58 with an appended checksum, using the given
+/// [`Hashing`] implementation for the SS58 checksum.
+pub fn encode_with_hashing<H: Hashing>(
+    payload: &[u8],
+    checksum: ChecksumKind,
+    hashing: &H,
+) -> String {
+    let mut bytes = payload.to_vec();
+    let full_checksum = compute_checksum(hashing, checksum, payload);
+    bytes.extend_from_slice(&full_checksum[..checksum.checksum_len()]);
+    bs58::encode(&bytes).into_string()
+}
+
+/// Decodes a base58-with-checksum string, using [`DefaultHashing`].
+#[cfg(feature = "default-crypto")]
+pub fn decode(s: &str, checksum: ChecksumKind) -> Result<Vec<u8>, Base58Error> {
+    decode_with_hashing(s, checksum, &DefaultHashing)
+}
+
+/// Decodes a base58-with-checksum string, using the given [`Hashing`]
+/// implementation for the SS58 checksum.
+pub fn decode_with_hashing<H: Hashing>(
+    s: &str,
+    checksum: ChecksumKind,
+    hashing: &H,
+) -> Result<Vec<u8>, Base58Error> {
+    let bytes = bs58::decode(s).into_vec().map_err(Base58Error::from_reason)?;
+    let checksum_len = checksum.checksum_len();
+    if bytes.len() < checksum_len {
+        return Err(Base58Error::from_str("Too short to contain a checksum"));
+    }
+    let (payload, mac) = bytes.split_at(bytes.len() - checksum_len);
+    let expected = compute_checksum(hashing, checksum, payload);
+    if mac != &expected[..checksum_len] {
+        return Err(Base58Error::from_str("Checksum mismatch"));
+    }
+    Ok(payload.to_vec())
+}
+
+fn compute_checksum<H: Hashing>(hashing: &H, checksum: ChecksumKind, payload: &[u8]) -> Vec<u8> {
+    match checksum {
+        ChecksumKind::Ss58 { .. } => {
+            let mut buf = Vec::with_capacity(b"SS58PRE".len() + payload.len());
+            buf.extend_from_slice(b"SS58PRE");
+            buf.extend_from_slice(payload);
+            hashing.blake2b_512(&buf).to_vec()
+        }
+        #[cfg(feature = "default-crypto")]
+        ChecksumKind::Bitcoin => {
+            use sha2::{Digest, Sha256};
+            let once = Sha256::digest(payload);
+            let twice = Sha256::digest(once);
+            twice.to_vec()
+        }
+    }
+}
+
+/// Error returned when a base58check string cannot be decoded.
+#[derive(Debug)]
+pub struct Base58Error {
+    reason: String,
+}
+
+impl Base58Error {
+    fn from_reason<E: Display>(reason: E) -> Self {
+        Base58Error::from_str(format!("{}", reason))
+    }
+
+    fn from_str<S: Into<String>>(reason: S) -> Self {
+        Base58Error { reason: reason.into() }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Base58Error {}
+
+impl Display for Base58Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid base58check string ({})", self.reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, ChecksumKind};
+
+    #[test]
+    fn can_round_trip_ss58_payload() {
+        let payload = [1u8; 33];
+        let checksum = ChecksumKind::ss58_for_body_len(32);
+        let encoded = encode(&payload, checksum);
+        assert_eq!(decode(&encoded, checksum).unwrap(), payload);
+    }
+
+    #[test]
+    fn can_round_trip_bitcoin_payload() {
+        let payload = [0u8; 21];
+        let encoded = encode(&payload, ChecksumKind::Bitcoin);
+        assert_eq!(decode(&encoded, ChecksumKind::Bitcoin).unwrap(), payload);
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!(decode("not valid base58!!!", ChecksumKind::Bitcoin).is_err());
+    }
+
+    #[test]
+    fn oversized_checksum_len_is_clamped_instead_of_panicking() {
+        let payload = [9u8; 4];
+        let checksum = ChecksumKind::Ss58 { checksum_len: 1000 };
+        let encoded = encode(&payload, checksum);
+        assert_eq!(decode(&encoded, checksum).unwrap(), payload);
+    }
+
+    #[test]
+    fn rejects_tampered_checksum() {
+        let payload = [5u8; 21];
+        let mut encoded = encode(&payload, ChecksumKind::Bitcoin);
+        encoded.push('1');
+        assert!(decode(&encoded, ChecksumKind::Bitcoin).is_err());
+    }
+}
@@ -0,0 +1,83 @@
+// Copyright (C) 2020 Stephane Raux. Distributed under the MIT license.
+
+//! Pluggable hashing primitives.
+//!
+//! [`Hashing`] abstracts over the hash functions this crate needs so it can
+//! run in environments that cannot or do not want to link `blake2b_simd`
+//! and `twox_hash` directly, such as a Wasm runtime that exposes its own
+//! host-function-backed hashing. [`DefaultHashing`] is the implementation
+//! used by this crate's convenience functions and is enabled by the
+//! `default-crypto` feature.
+
+/// Hash functions needed to derive SS58 addresses and storage keys.
+pub trait Hashing {
+    /// 512-bit Blake2b digest, used for the SS58 checksum.
+    fn blake2b_512(&self, input: &[u8]) -> [u8; 64];
+
+    /// 128-bit Blake2b digest, used by the `Blake2_128` storage hasher.
+    fn blake2b_128(&self, input: &[u8]) -> [u8; 16];
+
+    /// 256-bit Blake2b digest, used to derive SURI seeds and hierarchical
+    /// key derivation chain codes and junctions.
+    fn blake2b_256(&self, input: &[u8]) -> [u8; 32];
+
+    /// 128-bit xxHash, used to hash pallet/item names and by the
+    /// `Twox128` storage hasher.
+    fn twox_128(&self, input: &[u8]) -> [u8; 16];
+
+    /// 64-bit xxHash, used by the `Twox64Concat` storage hasher.
+    fn twox_64(&self, input: &[u8]) -> [u8; 8];
+}
+
+/// [`Hashing`] implementation backed by `blake2b_simd` and `twox_hash`.
+#[cfg(feature = "default-crypto")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultHashing;
+
+#[cfg(feature = "default-crypto")]
+impl Hashing for DefaultHashing {
+    fn blake2b_512(&self, input: &[u8]) -> [u8; 64] {
+        use core::convert::TryInto;
+        blake2b_simd::blake2b(input).as_bytes().try_into().unwrap()
+    }
+
+    fn blake2b_128(&self, input: &[u8]) -> [u8; 16] {
+        use core::convert::TryInto;
+        blake2b_simd::Params::new()
+            .hash_length(16)
+            .hash(input)
+            .as_bytes()
+            .try_into()
+            .unwrap()
+    }
+
+    fn blake2b_256(&self, input: &[u8]) -> [u8; 32] {
+        use core::convert::TryInto;
+        blake2b_simd::Params::new()
+            .hash_length(32)
+            .hash(input)
+            .as_bytes()
+            .try_into()
+            .unwrap()
+    }
+
+    fn twox_128(&self, input: &[u8]) -> [u8; 16] {
+        let mut out = [0; 16];
+        for (seed, chunk) in out.chunks_mut(8).enumerate() {
+            chunk.copy_from_slice(&twox_64_seeded(seed as u64, input));
+        }
+        out
+    }
+
+    fn twox_64(&self, input: &[u8]) -> [u8; 8] {
+        twox_64_seeded(0, input)
+    }
+}
+
+#[cfg(feature = "default-crypto")]
+fn twox_64_seeded(seed: u64, input: &[u8]) -> [u8; 8] {
+    use core::hash::Hasher;
+    let mut hasher = twox_hash::XxHash64::with_seed(seed);
+    hasher.write(input);
+    hasher.finish().to_le_bytes()
+}
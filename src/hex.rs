@@ -0,0 +1,55 @@
+// Copyright (C) 2020 Stephane Raux. Distributed under the MIT license.
+
+//! Hex encode/decode helpers shared by the `keystore` and `suri` modules.
+
+#[cfg(feature = "keystore")]
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[cfg(feature = "keystore")]
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    use core::fmt::Write;
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(s, "{:02x}", byte).expect("writing to a String never fails");
+    }
+    s
+}
+
+pub(crate) fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    let bytes = s.as_bytes();
+    if bytes.len() % 2 != 0 {
+        return None;
+    }
+    bytes
+        .chunks(2)
+        .map(|pair| {
+            let hi = (pair[0] as char).to_digit(16)?;
+            let lo = (pair[1] as char).to_digit(16)?;
+            Some((hi * 16 + lo) as u8)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_hex;
+    use alloc::vec;
+
+    #[test]
+    fn decodes_lowercase_hex() {
+        assert_eq!(decode_hex("00ff"), Some(vec![0, 255]));
+    }
+
+    #[test]
+    fn rejects_odd_length() {
+        assert_eq!(decode_hex("abc"), None);
+    }
+
+    #[test]
+    fn rejects_multi_byte_char_instead_of_panicking_on_char_boundary() {
+        // "aéb" is 4 bytes, passing the even-length check, but 'é' is a
+        // two-byte UTF-8 character with no char boundary between its bytes.
+        assert_eq!(decode_hex("aéb"), None);
+    }
+}
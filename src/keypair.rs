@@ -0,0 +1,152 @@
+// Copyright (C) 2020 Stephane Raux. Distributed under the MIT license.
+
+//! Key pairs for signing and verifying payloads, and for deriving
+//! [`AccountId`]s.
+//!
+//! Requires the `keypair` feature.
+
+use crate::AccountId;
+use alloc::vec::Vec;
+
+const SIGNING_CONTEXT: &[u8] = b"substrate";
+
+/// Signature scheme used by a [`KeyPair`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Scheme {
+    Sr25519,
+    Ed25519,
+}
+
+/// A key pair able to sign payloads.
+pub enum KeyPair {
+    Sr25519(schnorrkel::Keypair),
+    Ed25519(ed25519_dalek::Keypair),
+}
+
+impl KeyPair {
+    /// Derives a key pair from a 32-byte seed.
+    pub fn from_seed(scheme: Scheme, seed: &[u8; 32]) -> Self {
+        match scheme {
+            Scheme::Sr25519 => KeyPair::Sr25519(sr25519_keypair_from_seed(seed)),
+            Scheme::Ed25519 => {
+                let secret = ed25519_dalek::SecretKey::from_bytes(seed)
+                    .expect("a 32-byte seed is a valid ed25519 secret key");
+                let public = ed25519_dalek::PublicKey::from(&secret);
+                KeyPair::Ed25519(ed25519_dalek::Keypair { secret, public })
+            }
+        }
+    }
+
+    /// Returns the public key of this key pair.
+    pub fn public(&self) -> PublicKey {
+        match self {
+            KeyPair::Sr25519(pair) => PublicKey::Sr25519(pair.public),
+            KeyPair::Ed25519(pair) => PublicKey::Ed25519(pair.public),
+        }
+    }
+
+    /// Signs `message`.
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        match self {
+            KeyPair::Sr25519(pair) => {
+                let context = schnorrkel::signing_context(SIGNING_CONTEXT);
+                Signature::Sr25519(pair.sign(context.bytes(message)))
+            }
+            KeyPair::Ed25519(pair) => {
+                use ed25519_dalek::Signer;
+                Signature::Ed25519(pair.sign(message))
+            }
+        }
+    }
+}
+
+fn sr25519_keypair_from_seed(seed: &[u8; 32]) -> schnorrkel::Keypair {
+    schnorrkel::MiniSecretKey::from_bytes(seed)
+        .expect("a 32-byte seed is a valid sr25519 mini secret key")
+        .expand_to_keypair(schnorrkel::ExpansionMode::Ed25519)
+}
+
+/// A public key, identifying an account when paired with a signature
+/// scheme.
+#[derive(Clone, Copy)]
+pub enum PublicKey {
+    Sr25519(schnorrkel::PublicKey),
+    Ed25519(ed25519_dalek::PublicKey),
+}
+
+impl PublicKey {
+    /// Returns the public key bytes.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        match self {
+            PublicKey::Sr25519(key) => key.to_bytes(),
+            PublicKey::Ed25519(key) => key.to_bytes(),
+        }
+    }
+
+    /// Verifies that `signature` is a valid signature of `message` by this
+    /// public key.
+    pub fn verify(&self, message: &[u8], signature: &Signature) -> bool {
+        match (self, signature) {
+            (PublicKey::Sr25519(key), Signature::Sr25519(signature)) => {
+                let context = schnorrkel::signing_context(SIGNING_CONTEXT);
+                key.verify(context.bytes(message), signature).is_ok()
+            }
+            (PublicKey::Ed25519(key), Signature::Ed25519(signature)) => {
+                use ed25519_dalek::Verifier;
+                key.verify(message, signature).is_ok()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl From<PublicKey> for AccountId {
+    fn from(key: PublicKey) -> Self {
+        AccountId(key.to_bytes())
+    }
+}
+
+/// A signature produced by a [`KeyPair`].
+pub enum Signature {
+    Sr25519(schnorrkel::Signature),
+    Ed25519(ed25519_dalek::Signature),
+}
+
+impl Signature {
+    /// Returns the signature bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Signature::Sr25519(signature) => signature.to_bytes().to_vec(),
+            Signature::Ed25519(signature) => signature.to_bytes().to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyPair, Scheme};
+    use crate::AccountId;
+
+    #[test]
+    fn sr25519_signature_verifies_against_matching_public_key() {
+        let pair = KeyPair::from_seed(Scheme::Sr25519, &[1; 32]);
+        let signature = pair.sign(b"hello");
+        assert!(pair.public().verify(b"hello", &signature));
+        assert!(!pair.public().verify(b"goodbye", &signature));
+    }
+
+    #[test]
+    fn ed25519_signature_verifies_against_matching_public_key() {
+        let pair = KeyPair::from_seed(Scheme::Ed25519, &[2; 32]);
+        let signature = pair.sign(b"hello");
+        assert!(pair.public().verify(b"hello", &signature));
+        assert!(!pair.public().verify(b"goodbye", &signature));
+    }
+
+    #[test]
+    fn public_key_converts_into_account_id() {
+        let pair = KeyPair::from_seed(Scheme::Sr25519, &[3; 32]);
+        let account: AccountId = pair.public().into();
+        assert_eq!(account, AccountId(pair.public().to_bytes()));
+    }
+}
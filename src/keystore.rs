@@ -0,0 +1,285 @@
+// Copyright (C) 2020 Stephane Raux. Distributed under the MIT license.
+
+//! An encrypted JSON keystore for persisting account secrets, following
+//! the Web3 Secret Storage convention: a password-derived scrypt key
+//! encrypts the secret with AES-128-CTR, and a Blake2b MAC authenticates
+//! the ciphertext.
+//!
+//! A keystore holds whatever secret bytes the caller gives it, such as
+//! the 32-byte seed passed to [`crate::KeyPair::from_seed`], so a
+//! generated account can be written to and read back from disk.
+//!
+//! [`Keystore::encrypt`]/[`Keystore::decrypt`] use [`crate::DefaultHashing`]
+//! for the MAC; use [`Keystore::encrypt_with_hashing`]/
+//! [`Keystore::decrypt_with_hashing`] to supply a different [`crate::Hashing`]
+//! implementation.
+//!
+//! Requires the `keystore` feature.
+
+use crate::hex::{decode_hex, encode_hex};
+#[cfg(feature = "default-crypto")]
+use crate::DefaultHashing;
+use crate::Hashing;
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{KeyIvInit, StreamCipher};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::fmt::{self, Display};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+type Aes128Ctr64Be = ctr::Ctr64BE<aes::Aes128>;
+
+/// Scrypt key derivation function parameters.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScryptParams {
+    pub n: u32,
+    pub r: u32,
+    pub p: u32,
+    pub dk_len: usize,
+    pub salt: Vec<u8>,
+}
+
+impl Default for ScryptParams {
+    fn default() -> Self {
+        ScryptParams { n: 8192, r: 8, p: 1, dk_len: 32, salt: Vec::new() }
+    }
+}
+
+/// Parameters needed to encrypt a secret, besides the password.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KeystoreParams {
+    pub kdf: ScryptParams,
+    pub iv: [u8; 16],
+}
+
+/// An encrypted secret, serializable to and from JSON.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Json {
+    pub cipher: String,
+    pub ciphertext: HexBytes,
+    pub cipherparams: CipherParams,
+    pub kdf: String,
+    pub kdfparams: KdfParams,
+    pub mac: HexBytes,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CipherParams {
+    pub iv: HexBytes,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct KdfParams {
+    pub dklen: usize,
+    pub n: u32,
+    pub p: u32,
+    pub r: u32,
+    pub salt: HexBytes,
+}
+
+/// Byte string serialized as a hex string in keystore JSON.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HexBytes(pub Vec<u8>);
+
+impl Serialize for HexBytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode_hex(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for HexBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        decode_hex(&s).map(HexBytes).ok_or_else(|| D::Error::custom("invalid hex string"))
+    }
+}
+
+/// Encrypts and decrypts secrets into the keystore JSON format.
+pub struct Keystore;
+
+impl Keystore {
+    /// Encrypts `secret` with a key derived from `password`, using
+    /// [`DefaultHashing`].
+    ///
+    /// Panics if `params.kdf` is invalid, e.g. its `dk_len` is smaller than
+    /// 32 bytes or its `n` is not a power of two.
+    #[cfg(feature = "default-crypto")]
+    pub fn encrypt(secret: &[u8], password: &[u8], params: KeystoreParams) -> Json {
+        Self::encrypt_with_hashing(secret, password, params, &DefaultHashing)
+    }
+
+    /// Encrypts `secret` with a key derived from `password`, using the given
+    /// [`Hashing`] implementation for the MAC.
+    ///
+    /// Panics if `params.kdf` is invalid, e.g. its `dk_len` is smaller than
+    /// 32 bytes or its `n` is not a power of two.
+    pub fn encrypt_with_hashing<H: Hashing>(
+        secret: &[u8],
+        password: &[u8],
+        params: KeystoreParams,
+        hashing: &H,
+    ) -> Json {
+        let derived_key = derive_key(password, &params.kdf).expect("valid scrypt parameters");
+        let mut ciphertext = secret.to_vec();
+        let key = GenericArray::from_slice(&derived_key[..16]);
+        let iv = GenericArray::from_slice(&params.iv);
+        let mut cipher = Aes128Ctr64Be::new(key, iv);
+        cipher.apply_keystream(&mut ciphertext);
+        let mac = compute_mac(&derived_key, &ciphertext, hashing);
+        Json {
+            cipher: "aes-128-ctr".into(),
+            ciphertext: HexBytes(ciphertext),
+            cipherparams: CipherParams { iv: HexBytes(params.iv.to_vec()) },
+            kdf: "scrypt".into(),
+            kdfparams: KdfParams {
+                dklen: params.kdf.dk_len,
+                n: params.kdf.n,
+                p: params.kdf.p,
+                r: params.kdf.r,
+                salt: HexBytes(params.kdf.salt),
+            },
+            mac: HexBytes(mac),
+        }
+    }
+
+    /// Decrypts a keystore JSON with `password`, using [`DefaultHashing`],
+    /// returning the secret.
+    #[cfg(feature = "default-crypto")]
+    pub fn decrypt(json: &Json, password: &[u8]) -> Result<Vec<u8>, BadKeystore> {
+        Self::decrypt_with_hashing(json, password, &DefaultHashing)
+    }
+
+    /// Decrypts a keystore JSON with `password`, using the given [`Hashing`]
+    /// implementation for the MAC, returning the secret.
+    pub fn decrypt_with_hashing<H: Hashing>(
+        json: &Json,
+        password: &[u8],
+        hashing: &H,
+    ) -> Result<Vec<u8>, BadKeystore> {
+        if json.cipher != "aes-128-ctr" {
+            return Err(BadKeystore::from_str("Unsupported cipher"));
+        }
+        if json.kdf != "scrypt" {
+            return Err(BadKeystore::from_str("Unsupported KDF"));
+        }
+        let kdf = ScryptParams {
+            n: json.kdfparams.n,
+            r: json.kdfparams.r,
+            p: json.kdfparams.p,
+            dk_len: json.kdfparams.dklen,
+            salt: json.kdfparams.salt.0.clone(),
+        };
+        let derived_key = derive_key(password, &kdf)?;
+        let mac = compute_mac(&derived_key, &json.ciphertext.0, hashing);
+        if !constant_time_eq(&mac, &json.mac.0) {
+            return Err(BadKeystore::from_str("MAC mismatch; wrong password or corrupt keystore"));
+        }
+        let iv: [u8; 16] = json.cipherparams.iv.0.as_slice().try_into()
+            .map_err(|_| BadKeystore::from_str("Invalid IV length"))?;
+        let mut secret = json.ciphertext.0.clone();
+        let key = GenericArray::from_slice(&derived_key[..16]);
+        let iv = GenericArray::from_slice(&iv);
+        let mut cipher = Aes128Ctr64Be::new(key, iv);
+        cipher.apply_keystream(&mut secret);
+        Ok(secret)
+    }
+}
+
+fn derive_key(password: &[u8], params: &ScryptParams) -> Result<Vec<u8>, BadKeystore> {
+    if params.dk_len < 32 {
+        return Err(BadKeystore::from_str("Scrypt dklen must be at least 32 bytes"));
+    }
+    if !params.n.is_power_of_two() {
+        return Err(BadKeystore::from_str("Scrypt N must be a power of two"));
+    }
+    let log_n = params.n.trailing_zeros() as u8;
+    let scrypt_params = scrypt::Params::new(log_n, params.r, params.p, params.dk_len)
+        .map_err(|_| BadKeystore::from_str("Invalid scrypt parameters"))?;
+    let mut derived_key = vec![0; params.dk_len];
+    scrypt::scrypt(password, &params.salt, &scrypt_params, &mut derived_key)
+        .map_err(|_| BadKeystore::from_str("Invalid scrypt output length"))?;
+    Ok(derived_key)
+}
+
+fn compute_mac<H: Hashing>(derived_key: &[u8], ciphertext: &[u8], hashing: &H) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(16 + ciphertext.len());
+    buf.extend_from_slice(&derived_key[16..32]);
+    buf.extend_from_slice(ciphertext);
+    hashing.blake2b_512(&buf).to_vec()
+}
+
+/// Compares two byte strings in constant time, to avoid a timing oracle
+/// when checking a MAC derived from an attacker-controlled keystore.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Error returned when a keystore cannot be decrypted.
+#[derive(Debug)]
+pub struct BadKeystore {
+    reason: String,
+}
+
+impl BadKeystore {
+    fn from_str<S: Into<String>>(reason: S) -> Self {
+        BadKeystore { reason: reason.into() }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BadKeystore {}
+
+impl Display for BadKeystore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid keystore ({})", self.reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Keystore, KeystoreParams, ScryptParams};
+    use alloc::vec;
+
+    fn test_params() -> KeystoreParams {
+        KeystoreParams {
+            kdf: ScryptParams { n: 2, r: 1, p: 1, dk_len: 32, salt: vec![1; 16] },
+            iv: [2; 16],
+        }
+    }
+
+    #[test]
+    fn can_round_trip_a_secret() {
+        let secret = [7u8; 32];
+        let json = Keystore::encrypt(&secret, b"correct horse", test_params());
+        let decrypted = Keystore::decrypt(&json, b"correct horse").unwrap();
+        assert_eq!(decrypted, secret);
+    }
+
+    #[test]
+    fn rejects_wrong_password() {
+        let secret = [7u8; 32];
+        let json = Keystore::encrypt(&secret, b"correct horse", test_params());
+        assert!(Keystore::decrypt(&json, b"wrong password").is_err());
+    }
+
+    #[test]
+    fn rejects_undersized_dklen_instead_of_panicking() {
+        let secret = [7u8; 32];
+        let mut json = Keystore::encrypt(&secret, b"correct horse", test_params());
+        json.kdfparams.dklen = 16;
+        assert!(Keystore::decrypt(&json, b"correct horse").is_err());
+    }
+
+    #[test]
+    fn rejects_non_power_of_two_n_instead_of_panicking() {
+        let secret = [7u8; 32];
+        let mut json = Keystore::encrypt(&secret, b"correct horse", test_params());
+        json.kdfparams.n = 3;
+        assert!(Keystore::decrypt(&json, b"correct horse").is_err());
+    }
+}
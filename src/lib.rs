@@ -1,60 +1,278 @@
 // Copyright (C) 2020 Stephane Raux. Distributed under the MIT license.
 
 // #![deny(missing_docs)]
+#![cfg_attr(not(feature = "std"), no_std)]
 #![deny(warnings)]
 
-use blake2b_simd::blake2b;
+extern crate alloc;
+
+pub mod base58check;
+mod hashing;
+#[cfg(any(feature = "keypair", feature = "keystore"))]
+mod hex;
+#[cfg(feature = "keypair")]
+mod keypair;
+#[cfg(feature = "keypair")]
+mod suri;
+#[cfg(feature = "keystore")]
+mod keystore;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::fmt::{self, Display};
+use core::str::FromStr;
 use parity_scale_codec::{Decode, Encode};
-use std::convert::TryInto;
-use std::error::Error as StdError;
-use std::fmt::{self, Display};
-use std::hash::Hasher;
-use std::str::FromStr;
-use twox_hash::XxHash64;
 
+use crate::base58check::ChecksumKind;
+
+pub use crate::hashing::Hashing;
+#[cfg(feature = "default-crypto")]
+pub use crate::hashing::DefaultHashing;
+#[cfg(feature = "keypair")]
+pub use crate::keypair::{KeyPair, PublicKey, Scheme, Signature};
+#[cfg(feature = "keypair")]
+pub use crate::suri::{BadSuri, DeriveJunction, Suri};
+#[cfg(feature = "keystore")]
+pub use crate::keystore::{
+    BadKeystore, CipherParams, HexBytes, Json, KdfParams, Keystore, KeystoreParams, ScryptParams,
+};
+
+/// Builds the 128-bit storage key prefix for a plain storage value, using
+/// [`DefaultHashing`].
+#[cfg(feature = "default-crypto")]
 pub fn storage_key(module: &str, item: &str) -> u128 {
-    let low = hash_with_space(XxHash64::with_seed(0), module, item) as u128;
-    let high = hash_with_space(XxHash64::with_seed(1), module, item) as u128;
-    let key = high << 64 | low;
-    u128::from_be(key.to_le())
+    storage_key_with_hashing(module, item, &DefaultHashing)
+}
+
+/// Builds the 128-bit storage key prefix for a plain storage value, using
+/// the given [`Hashing`] implementation.
+pub fn storage_key_with_hashing<H: Hashing>(module: &str, item: &str, hashing: &H) -> u128 {
+    let combined = format!("{} {}", module, item);
+    u128::from_be_bytes(hashing.twox_128(combined.as_bytes()))
+}
+
+/// Hashing algorithm used by a Substrate storage map to combine a key with
+/// the map's prefix.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StorageHasher {
+    Blake2_128,
+    Blake2_128Concat,
+    Twox64Concat,
+    Twox128,
+    Identity,
+}
+
+/// Builds the storage key for a value in a storage map, using
+/// [`DefaultHashing`].
+///
+/// `key` must already be SCALE-encoded (see `parity_scale_codec::Encode`).
+#[cfg(feature = "default-crypto")]
+pub fn storage_map_key(module: &str, item: &str, key: &[u8], hasher: StorageHasher) -> Vec<u8> {
+    storage_map_key_with_hashing(module, item, key, hasher, &DefaultHashing)
 }
 
-fn hash_with_space<H: Hasher>(mut hasher: H, left: &str, right: &str) -> u64 {
-    hasher.write(left.as_bytes());
-    hasher.write_u8(b' ');
-    hasher.write(right.as_bytes());
-    hasher.finish()
+/// Builds the storage key for a value in a storage map, using the given
+/// [`Hashing`] implementation.
+///
+/// `key` must already be SCALE-encoded (see `parity_scale_codec::Encode`).
+pub fn storage_map_key_with_hashing<H: Hashing>(
+    module: &str,
+    item: &str,
+    key: &[u8],
+    hasher: StorageHasher,
+    hashing: &H,
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(32 + key.len() + 16);
+    out.extend_from_slice(&hashing.twox_128(module.as_bytes()));
+    out.extend_from_slice(&hashing.twox_128(item.as_bytes()));
+    out.extend(hash_storage_key(hashing, hasher, key));
+    out
+}
+
+/// Builds the storage key for a value in a storage double map, using
+/// [`DefaultHashing`].
+///
+/// `key1` and `key2` must already be SCALE-encoded.
+#[cfg(feature = "default-crypto")]
+pub fn storage_double_map_key(
+    module: &str,
+    item: &str,
+    key1: &[u8],
+    hasher1: StorageHasher,
+    key2: &[u8],
+    hasher2: StorageHasher,
+) -> Vec<u8> {
+    storage_double_map_key_with_hashing(module, item, key1, hasher1, key2, hasher2, &DefaultHashing)
+}
+
+/// Builds the storage key for a value in a storage double map, using the
+/// given [`Hashing`] implementation.
+///
+/// `key1` and `key2` must already be SCALE-encoded.
+pub fn storage_double_map_key_with_hashing<H: Hashing>(
+    module: &str,
+    item: &str,
+    key1: &[u8],
+    hasher1: StorageHasher,
+    key2: &[u8],
+    hasher2: StorageHasher,
+    hashing: &H,
+) -> Vec<u8> {
+    let mut out = storage_map_key_with_hashing(module, item, key1, hasher1, hashing);
+    out.extend(hash_storage_key(hashing, hasher2, key2));
+    out
+}
+
+fn hash_storage_key<H: Hashing>(hashing: &H, hasher: StorageHasher, key: &[u8]) -> Vec<u8> {
+    match hasher {
+        StorageHasher::Blake2_128 => hashing.blake2b_128(key).to_vec(),
+        StorageHasher::Blake2_128Concat => {
+            let mut out = hashing.blake2b_128(key).to_vec();
+            out.extend_from_slice(key);
+            out
+        }
+        StorageHasher::Twox64Concat => {
+            let mut out = hashing.twox_64(key).to_vec();
+            out.extend_from_slice(key);
+            out
+        }
+        StorageHasher::Twox128 => hashing.twox_128(key).to_vec(),
+        StorageHasher::Identity => key.to_vec(),
+    }
 }
 
 #[derive(Clone, Debug, Decode, Encode, Eq, Hash, PartialEq, PartialOrd)]
 pub struct AccountId([u8; 32]);
 
 impl AccountId {
+    /// Formats this account ID into its SS58 representation using the
+    /// default Substrate network prefix (42) and [`DefaultHashing`].
+    #[cfg(feature = "default-crypto")]
     pub fn to_string(&self) -> String {
-        let mut bytes = vec![42];
-        bytes.extend(&self.0);
-        let hash = hash_account(&bytes);
-        bytes.extend(&hash.as_array()[0..2]);
-        bs58::encode(&bytes).into_string()
+        self.to_ss58check_with_prefix(Ss58Format::default())
+    }
+
+    /// Formats this account ID as an SS58 address for the network
+    /// identified by `format`, using [`DefaultHashing`].
+    #[cfg(feature = "default-crypto")]
+    pub fn to_ss58check_with_prefix(&self, format: Ss58Format) -> String {
+        self.to_ss58check_with_prefix_and_hashing(format, &DefaultHashing)
+    }
+
+    /// Formats this account ID as an SS58 address for the network
+    /// identified by `format`, using the given [`Hashing`] implementation.
+    pub fn to_ss58check_with_prefix_and_hashing<H: Hashing>(
+        &self,
+        format: Ss58Format,
+        hashing: &H,
+    ) -> String {
+        let mut payload = ss58_prefix_bytes(format.prefix());
+        payload.extend_from_slice(&self.0);
+        let checksum = ChecksumKind::ss58_for_body_len(self.0.len());
+        base58check::encode_with_hashing(&payload, checksum, hashing)
+    }
+
+    /// Parses an SS58 address, returning the account ID along with the
+    /// network prefix it was encoded with, using [`DefaultHashing`].
+    #[cfg(feature = "default-crypto")]
+    pub fn from_ss58check(s: &str) -> Result<(Self, Ss58Format), BadAccountId> {
+        Self::from_ss58check_with_hashing(s, &DefaultHashing)
+    }
+
+    /// Parses an SS58 address, returning the account ID along with the
+    /// network prefix it was encoded with, using the given [`Hashing`]
+    /// implementation.
+    pub fn from_ss58check_with_hashing<H: Hashing>(
+        s: &str,
+        hashing: &H,
+    ) -> Result<(Self, Ss58Format), BadAccountId> {
+        let checksum = ChecksumKind::ss58_for_body_len(32);
+        let payload = base58check::decode_with_hashing(s, checksum, hashing)
+            .map_err(BadAccountId::from_reason)?;
+        let (prefix, prefix_len) = decode_ss58_prefix(&payload)?;
+        let body = &payload[prefix_len..];
+        if body.len() != 32 {
+            return Err(BadAccountId::from_str(
+                format!("Expected 32 bytes in account ID but found {}", body.len())
+            ))
+        }
+        Ok((AccountId(body.try_into().unwrap()), Ss58Format::new(prefix)))
     }
 }
 
+#[cfg(feature = "default-crypto")]
 impl FromStr for AccountId {
     type Err = BadAccountId;
 
     fn from_str(s: &str) -> Result<Self, BadAccountId> {
-        let bytes = bs58::decode(s).into_vec().map_err(BadAccountId::from_reason)?;
-        if bytes.len() != 35 {
-            return Err(BadAccountId::from_str(
-                format!("Expected 35 bytes in account ID but found {}", bytes.len())
-            ))
-        }
-        let account = &bytes[1..33];
-        let hash = hash_account(&bytes[..33]);
-        if bytes[33..] != hash.as_array()[0..2] {
-            return Err(BadAccountId::from_str("Invalid hash in account ID"))
+        AccountId::from_ss58check(s).map(|(account, _)| account)
+    }
+}
+
+/// SS58 network identifier used to format and parse addresses.
+///
+/// See <https://github.com/paritytech/substrate/wiki/External-Address-Format-(SS58)>.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Ss58Format(u16);
+
+impl Ss58Format {
+    /// Generic Substrate network prefix.
+    pub const SUBSTRATE: Ss58Format = Ss58Format(42);
+
+    /// Polkadot network prefix.
+    pub const POLKADOT: Ss58Format = Ss58Format(0);
+
+    /// Kusama network prefix.
+    pub const KUSAMA: Ss58Format = Ss58Format(2);
+
+    pub fn new(prefix: u16) -> Self {
+        Ss58Format(prefix)
+    }
+
+    pub fn prefix(self) -> u16 {
+        self.0
+    }
+}
+
+impl Default for Ss58Format {
+    fn default() -> Self {
+        Ss58Format::SUBSTRATE
+    }
+}
+
+impl From<u16> for Ss58Format {
+    fn from(prefix: u16) -> Self {
+        Ss58Format::new(prefix)
+    }
+}
+
+fn ss58_prefix_bytes(prefix: u16) -> Vec<u8> {
+    if prefix < 64 {
+        vec![prefix as u8]
+    } else {
+        let ident = prefix & 0b0011_1111_1111_1111;
+        let first = 0b0100_0000 | ((ident & 0b0000_0000_1111_1100) >> 2);
+        let second = (ident >> 8) | ((ident & 0b11) << 6);
+        vec![first as u8, second as u8]
+    }
+}
+
+fn decode_ss58_prefix(bytes: &[u8]) -> Result<(u16, usize), BadAccountId> {
+    match bytes.first() {
+        None => Err(BadAccountId::from_str("Empty account ID")),
+        Some(&first) if first & 0b1100_0000 == 0b0100_0000 => {
+            let second = *bytes.get(1)
+                .ok_or_else(|| BadAccountId::from_str("Truncated account ID prefix"))?;
+            let ident = ((second as u16 >> 6) & 0b11)
+                | ((first as u16 & 0b0011_1111) << 2)
+                | ((second as u16 & 0b0011_1111) << 8);
+            Ok((ident, 2))
         }
-        Ok(AccountId(account.try_into().unwrap()))
+        Some(&first) if first < 64 => Ok((first as u16, 1)),
+        Some(_) => Err(BadAccountId::from_str("Invalid account ID prefix")),
     }
 }
 
@@ -64,8 +282,8 @@ pub struct BadAccountId {
 }
 
 impl BadAccountId {
-    fn from_reason<E: StdError>(reason: E) -> Self {
-        BadAccountId::from_str(reason.to_string())
+    fn from_reason<E: Display>(reason: E) -> Self {
+        BadAccountId::from_str(format!("{}", reason))
     }
 
     fn from_str<S>(reason: S) -> Self
@@ -77,7 +295,8 @@ impl BadAccountId {
     }
 }
 
-impl StdError for BadAccountId {}
+#[cfg(feature = "std")]
+impl std::error::Error for BadAccountId {}
 
 impl Display for BadAccountId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -85,13 +304,9 @@ impl Display for BadAccountId {
     }
 }
 
-fn hash_account(bytes: &[u8]) -> blake2b_simd::Hash {
-    blake2b(&[&b"SS58PRE"[..], bytes].concat())
-}
-
 #[cfg(test)]
 mod tests {
-    use crate::{AccountId, storage_key};
+    use crate::{storage_double_map_key, storage_key, storage_map_key, AccountId, StorageHasher};
     use std::convert::TryFrom;
 
     #[test]
@@ -99,6 +314,44 @@ mod tests {
         assert_eq!(storage_key("Sudo", "Key"), 0x50a63a871aced22e88ee6466fe5aa5d9);
     }
 
+    #[test]
+    fn storage_map_key_identity_appends_raw_key() {
+        let key = storage_map_key("System", "Account", &[1, 2, 3], StorageHasher::Identity);
+        assert_eq!(key.len(), 16 + 16 + 3);
+        assert_eq!(&key[32..], &[1, 2, 3]);
+    }
+
+    #[test]
+    fn storage_map_key_blake2_128_concat_appends_original_key_after_digest() {
+        let key = storage_map_key("System", "Account", &[1, 2, 3, 4], StorageHasher::Blake2_128Concat);
+        assert_eq!(key.len(), 16 + 16 + 16 + 4);
+        assert_eq!(&key[key.len() - 4..], &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn storage_map_key_twox64_concat_appends_original_key_after_digest() {
+        let key = storage_map_key("System", "Account", &[9, 9], StorageHasher::Twox64Concat);
+        assert_eq!(key.len(), 16 + 16 + 8 + 2);
+        assert_eq!(&key[key.len() - 2..], &[9, 9]);
+    }
+
+    #[test]
+    fn storage_double_map_key_concatenates_both_hashed_keys() {
+        let key1 = &[1, 2, 3][..];
+        let key2 = &[4, 5][..];
+        let key = storage_double_map_key(
+            "Staking",
+            "Bonded",
+            key1,
+            StorageHasher::Twox64Concat,
+            key2,
+            StorageHasher::Identity,
+        );
+        let expected_prefix = storage_map_key("Staking", "Bonded", key1, StorageHasher::Twox64Concat);
+        assert_eq!(&key[..expected_prefix.len()], &expected_prefix[..]);
+        assert_eq!(&key[expected_prefix.len()..], key2);
+    }
+
     const ACCOUNT_HEX: &str = "d43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d";
     const ACCOUNT_ID: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
 
@@ -122,4 +375,24 @@ mod tests {
     fn can_convert_string_to_account_id() {
         assert_eq!(ACCOUNT_ID.parse::<AccountId>().unwrap(), make_account_id());
     }
+
+    #[test]
+    fn can_round_trip_account_id_with_arbitrary_prefix() {
+        for &prefix in &[0, 2, 42, 63, 64, 128, 16383] {
+            let format = crate::Ss58Format::new(prefix);
+            let address = make_account_id().to_ss58check_with_prefix(format);
+            let (account, decoded_format) = AccountId::from_ss58check(&address).unwrap();
+            assert_eq!(account, make_account_id());
+            assert_eq!(decoded_format.prefix(), prefix);
+        }
+    }
+
+    #[test]
+    fn polkadot_and_kusama_prefixes_differ_from_substrate() {
+        let polkadot = make_account_id().to_ss58check_with_prefix(crate::Ss58Format::POLKADOT);
+        let kusama = make_account_id().to_ss58check_with_prefix(crate::Ss58Format::KUSAMA);
+        assert_ne!(polkadot, ACCOUNT_ID);
+        assert_ne!(kusama, ACCOUNT_ID);
+        assert_ne!(polkadot, kusama);
+    }
 }
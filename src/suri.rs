@@ -0,0 +1,360 @@
+// Copyright (C) 2020 Stephane Raux. Distributed under the MIT license.
+
+//! Secret URIs (`<phrase>//hard/soft///password`) and hierarchical key
+//! derivation.
+//!
+//! The base phrase is taken as raw seed material rather than decoded as a
+//! BIP-39 mnemonic: a `0x`-prefixed 64 hex digit phrase is used as the
+//! 32-byte seed verbatim, and any other phrase is UTF-8 bytes padded (or
+//! blake2b-256-hashed, if longer than 32 bytes) to 32 bytes. This is
+//! enough to derive children from a seed, but the derived keys are not
+//! the ones `subkey` derives for the same URI, well-known dev accounts
+//! such as `//Alice` included: besides the phrase not being decoded as a
+//! mnemonic, a hard junction is derived as the blake2b-256 hash of
+//! `"SubstrateHDKD"`, the junction's chain code and the parent seed,
+//! which is not `subkey`'s hard derivation for either scheme, even when
+//! its development seed is supplied as raw bytes. The optional
+//! `///password` is not a BIP-39 passphrase
+//! either (the phrase is never treated as a mnemonic); it is instead
+//! mixed into the derived seed, so supplying a different password always
+//! yields a different account.
+//!
+//! Parsing a secret URI hashes each junction's chain code eagerly (before
+//! any scheme or [`Hashing`] implementation is chosen), so [`FromStr`] and
+//! [`DeriveJunction::hard`]/[`DeriveJunction::soft`] require the
+//! `default-crypto` feature; use [`Suri::parse_with_hashing`] and
+//! [`DeriveJunction::hard_with_hashing`]/[`DeriveJunction::soft_with_hashing`]
+//! to parse without linking `blake2b_simd` directly.
+//!
+//! Requires the `keypair` feature.
+
+use crate::hex::decode_hex;
+use crate::keypair::{KeyPair, Scheme};
+use crate::Hashing;
+#[cfg(feature = "default-crypto")]
+use crate::DefaultHashing;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+use core::str::FromStr;
+use parity_scale_codec::Encode;
+use schnorrkel::derive::{ChainCode, Derivation};
+use schnorrkel::{ExpansionMode, MiniSecretKey};
+
+/// A parsed secret URI: a base seed phrase, an ordered derivation path,
+/// and an optional password.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Suri {
+    pub phrase: String,
+    pub junctions: Vec<DeriveJunction>,
+    pub password: Option<String>,
+}
+
+impl Suri {
+    /// Derives the key pair this secret URI describes, using
+    /// [`DefaultHashing`].
+    ///
+    /// Returns an error if the URI derives a soft junction for a scheme
+    /// that does not support it (ed25519).
+    #[cfg(feature = "default-crypto")]
+    pub fn derive(&self, scheme: Scheme) -> Result<KeyPair, BadSuri> {
+        self.derive_with_hashing(scheme, &DefaultHashing)
+    }
+
+    /// Derives the key pair this secret URI describes, using the given
+    /// [`Hashing`] implementation.
+    ///
+    /// Returns an error if the URI derives a soft junction for a scheme
+    /// that does not support it (ed25519).
+    pub fn derive_with_hashing<H: Hashing>(
+        &self,
+        scheme: Scheme,
+        hashing: &H,
+    ) -> Result<KeyPair, BadSuri> {
+        let seed = phrase_seed(&self.phrase, self.password.as_deref(), hashing);
+        match scheme {
+            Scheme::Sr25519 => Ok(derive_sr25519(&seed, &self.junctions, hashing)),
+            Scheme::Ed25519 => derive_ed25519(&seed, &self.junctions, hashing),
+        }
+    }
+}
+
+#[cfg(feature = "default-crypto")]
+impl FromStr for Suri {
+    type Err = BadSuri;
+
+    fn from_str(s: &str) -> Result<Self, BadSuri> {
+        Suri::parse_with_hashing(s, &DefaultHashing)
+    }
+}
+
+impl Suri {
+    /// Parses a secret URI, using the given [`Hashing`] implementation to
+    /// compute junction chain codes.
+    pub fn parse_with_hashing<H: Hashing>(s: &str, hashing: &H) -> Result<Self, BadSuri> {
+        let (without_password, password) = match s.find("///") {
+            Some(index) => (&s[..index], Some(s[index + 3..].to_string())),
+            None => (s, None),
+        };
+        let path_start = without_password.find('/').unwrap_or(without_password.len());
+        let phrase = &without_password[..path_start];
+        if phrase.is_empty() {
+            return Err(BadSuri::from_str("Missing seed phrase"));
+        }
+        let junctions = parse_junctions(&without_password[path_start..], hashing)?;
+        Ok(Suri { phrase: phrase.to_string(), junctions, password })
+    }
+}
+
+fn parse_junctions<H: Hashing>(
+    mut path: &str,
+    hashing: &H,
+) -> Result<Vec<DeriveJunction>, BadSuri> {
+    let mut junctions = Vec::new();
+    while !path.is_empty() {
+        let hard = path.starts_with("//");
+        path = &path[if hard { 2 } else { 1 }..];
+        let end = path.find('/').unwrap_or(path.len());
+        let (segment, rest) = path.split_at(end);
+        if segment.is_empty() {
+            return Err(BadSuri::from_str("Empty derivation junction"));
+        }
+        junctions.push(if hard {
+            DeriveJunction::hard_with_hashing(segment, hashing)
+        } else {
+            DeriveJunction::soft_with_hashing(segment, hashing)
+        });
+        path = rest;
+    }
+    Ok(junctions)
+}
+
+/// A single step ("junction") in a hierarchical key derivation path.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DeriveJunction {
+    Hard([u8; 32]),
+    Soft([u8; 32]),
+}
+
+impl DeriveJunction {
+    /// Builds a hard junction from `segment`, using [`DefaultHashing`].
+    #[cfg(feature = "default-crypto")]
+    pub fn hard(segment: &str) -> Self {
+        Self::hard_with_hashing(segment, &DefaultHashing)
+    }
+
+    /// Builds a hard junction from `segment`, using the given [`Hashing`]
+    /// implementation.
+    pub fn hard_with_hashing<H: Hashing>(segment: &str, hashing: &H) -> Self {
+        DeriveJunction::Hard(chain_code(segment, hashing))
+    }
+
+    /// Builds a soft junction from `segment`, using [`DefaultHashing`].
+    #[cfg(feature = "default-crypto")]
+    pub fn soft(segment: &str) -> Self {
+        Self::soft_with_hashing(segment, &DefaultHashing)
+    }
+
+    /// Builds a soft junction from `segment`, using the given [`Hashing`]
+    /// implementation.
+    pub fn soft_with_hashing<H: Hashing>(segment: &str, hashing: &H) -> Self {
+        DeriveJunction::Soft(chain_code(segment, hashing))
+    }
+}
+
+fn chain_code<H: Hashing>(segment: &str, hashing: &H) -> [u8; 32] {
+    let encoded = match segment.parse::<u64>() {
+        Ok(n) => n.encode(),
+        Err(_) => segment.encode(),
+    };
+    if encoded.len() > 32 {
+        hashing.blake2b_256(&encoded)
+    } else {
+        let mut chain_code = [0; 32];
+        chain_code[..encoded.len()].copy_from_slice(&encoded);
+        chain_code
+    }
+}
+
+fn derive_hard<H: Hashing>(seed: &[u8; 32], chain_code: &[u8; 32], hashing: &H) -> [u8; 32] {
+    let mut buf = Vec::with_capacity(b"SubstrateHDKD".len() + 32 + 32);
+    buf.extend_from_slice(b"SubstrateHDKD");
+    buf.extend_from_slice(chain_code);
+    buf.extend_from_slice(seed);
+    hashing.blake2b_256(&buf)
+}
+
+fn derive_sr25519<H: Hashing>(seed: &[u8; 32], junctions: &[DeriveJunction], hashing: &H) -> KeyPair {
+    let mut hard_seed = *seed;
+    let mut mini = MiniSecretKey::from_bytes(&hard_seed)
+        .expect("a 32-byte seed is a valid sr25519 mini secret key");
+    let mut secret = mini.expand(ExpansionMode::Ed25519);
+    for junction in junctions {
+        match junction {
+            DeriveJunction::Hard(chain_code) => {
+                hard_seed = derive_hard(&hard_seed, chain_code, hashing);
+                mini = MiniSecretKey::from_bytes(&hard_seed)
+                    .expect("a derived hard junction seed is a valid mini secret key");
+                secret = mini.expand(ExpansionMode::Ed25519);
+            }
+            DeriveJunction::Soft(chain_code) => {
+                let (derived, _) = secret.derived_key_simple(ChainCode(*chain_code), &[]);
+                secret = derived;
+            }
+        }
+    }
+    let public = secret.to_public();
+    KeyPair::Sr25519(schnorrkel::Keypair { secret, public })
+}
+
+fn derive_ed25519<H: Hashing>(
+    seed: &[u8; 32],
+    junctions: &[DeriveJunction],
+    hashing: &H,
+) -> Result<KeyPair, BadSuri> {
+    let mut seed = *seed;
+    for junction in junctions {
+        match junction {
+            DeriveJunction::Hard(chain_code) => seed = derive_hard(&seed, chain_code, hashing),
+            DeriveJunction::Soft(_) => {
+                return Err(BadSuri::from_str("ed25519 does not support soft derivation"))
+            }
+        }
+    }
+    Ok(KeyPair::from_seed(Scheme::Ed25519, &seed))
+}
+
+fn phrase_seed<H: Hashing>(phrase: &str, password: Option<&str>, hashing: &H) -> [u8; 32] {
+    let seed = raw_phrase_seed(phrase, hashing);
+    match password {
+        Some(password) => mix_password(&seed, password, hashing),
+        None => seed,
+    }
+}
+
+fn raw_phrase_seed<H: Hashing>(phrase: &str, hashing: &H) -> [u8; 32] {
+    if let Some(hex) = phrase.strip_prefix("0x") {
+        if let Some(bytes) = decode_hex(hex) {
+            if bytes.len() == 32 {
+                let mut seed = [0; 32];
+                seed.copy_from_slice(&bytes);
+                return seed;
+            }
+        }
+    }
+    let bytes = phrase.as_bytes();
+    if bytes.len() > 32 {
+        hashing.blake2b_256(bytes)
+    } else {
+        let mut seed = [0; 32];
+        seed[..bytes.len()].copy_from_slice(bytes);
+        seed
+    }
+}
+
+/// Mixes `password` into `seed`, so that deriving the same phrase with a
+/// different password yields an unrelated seed.
+fn mix_password<H: Hashing>(seed: &[u8; 32], password: &str, hashing: &H) -> [u8; 32] {
+    let mut buf = Vec::with_capacity(b"SuriPassword".len() + 32 + password.len());
+    buf.extend_from_slice(b"SuriPassword");
+    buf.extend_from_slice(seed);
+    buf.extend_from_slice(password.as_bytes());
+    hashing.blake2b_256(&buf)
+}
+
+/// Error returned when a secret URI cannot be parsed or derived.
+#[derive(Debug)]
+pub struct BadSuri {
+    reason: String,
+}
+
+impl BadSuri {
+    fn from_str<S: Into<String>>(reason: S) -> Self {
+        BadSuri { reason: reason.into() }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BadSuri {}
+
+impl Display for BadSuri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid secret URI ({})", self.reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeriveJunction, Suri};
+    use crate::keypair::Scheme;
+
+    #[test]
+    fn parses_phrase_with_hard_and_soft_junctions_and_password() {
+        let suri: Suri = "0x0101010101010101010101010101010101010101010101010101010101010101//hard/soft///pw"
+            .parse()
+            .unwrap();
+        assert_eq!(suri.phrase, "0x0101010101010101010101010101010101010101010101010101010101010101");
+        assert_eq!(suri.password.as_deref(), Some("pw"));
+        assert_eq!(suri.junctions.len(), 2);
+        assert!(matches!(suri.junctions[0], DeriveJunction::Hard(_)));
+        assert!(matches!(suri.junctions[1], DeriveJunction::Soft(_)));
+    }
+
+    #[test]
+    fn parses_phrase_without_junctions_or_password() {
+        let suri: Suri = "Alice".parse().unwrap();
+        assert_eq!(suri.phrase, "Alice");
+        assert!(suri.junctions.is_empty());
+        assert_eq!(suri.password, None);
+    }
+
+    #[test]
+    fn rejects_empty_phrase() {
+        assert!("//Alice".parse::<Suri>().is_err());
+    }
+
+    #[test]
+    fn sr25519_derivation_is_deterministic() {
+        let suri: Suri = "0x0101010101010101010101010101010101010101010101010101010101010101//Alice"
+            .parse()
+            .unwrap();
+        let a = suri.derive(Scheme::Sr25519).unwrap();
+        let b = suri.derive(Scheme::Sr25519).unwrap();
+        assert_eq!(a.public().to_bytes(), b.public().to_bytes());
+    }
+
+    #[test]
+    fn ed25519_rejects_soft_junctions() {
+        let suri: Suri = "0x0101010101010101010101010101010101010101010101010101010101010101/soft"
+            .parse()
+            .unwrap();
+        assert!(suri.derive(Scheme::Ed25519).is_err());
+    }
+
+    #[test]
+    fn different_passwords_derive_different_keys() {
+        let without_password: Suri = "0x0101010101010101010101010101010101010101010101010101010101010101//Alice"
+            .parse()
+            .unwrap();
+        let password1: Suri = "0x0101010101010101010101010101010101010101010101010101010101010101//Alice///secret1"
+            .parse()
+            .unwrap();
+        let password2: Suri = "0x0101010101010101010101010101010101010101010101010101010101010101//Alice///secret2"
+            .parse()
+            .unwrap();
+        let a = without_password.derive(Scheme::Sr25519).unwrap();
+        let b = password1.derive(Scheme::Sr25519).unwrap();
+        let c = password2.derive(Scheme::Sr25519).unwrap();
+        assert_ne!(a.public().to_bytes(), b.public().to_bytes());
+        assert_ne!(b.public().to_bytes(), c.public().to_bytes());
+    }
+
+    #[test]
+    fn phrase_with_non_hex_multi_byte_char_falls_back_to_utf8_seed_instead_of_panicking() {
+        // "aéb" is 4 bytes, an even length that passes decode_hex's
+        // length check, but 'é' is a two-byte UTF-8 character with no
+        // char boundary between its bytes.
+        let suri: Suri = "0xaéb//Alice".parse().unwrap();
+        assert!(suri.derive(Scheme::Sr25519).is_ok());
+    }
+}